@@ -0,0 +1,152 @@
+//! Derive macro for the `/proc` column-parsing pattern that recurs throughout `procfs-core`.
+//!
+//! `#[derive(ParseColumns)]` reads a struct's fields in declaration order and generates a
+//! `parse_line(line: &str) -> procfs_core::ProcResult<Self>` associated function that consumes
+//! one whitespace-delimited column per field: numeric fields are parsed with `FromStr`, `String`
+//! fields are taken verbatim, and a handful of field attributes adjust the default behavior:
+//!
+//! - `#[proc(range)]` parses a `lower-upper` token into a `RangeInclusive<T>` (the bound type `T`
+//!   is taken from the field itself), falling back to a single repeated value (`N` becomes
+//!   `N..=N`) when there's no dash.
+//! - `#[proc(rest)]` collects every remaining column into the field instead of just one.
+//! - `#[proc(skip)]` consumes a column and fills the field with `Default::default()` instead of
+//!   anything parsed from it.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Type};
+
+enum ColumnKind {
+    Plain,
+    Range,
+    Rest,
+    Skip,
+}
+
+fn column_kind(attrs: &[syn::Attribute]) -> ColumnKind {
+    for attr in attrs {
+        if !attr.path().is_ident("proc") {
+            continue;
+        }
+
+        let mut kind = ColumnKind::Plain;
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("range") {
+                kind = ColumnKind::Range;
+            } else if meta.path.is_ident("rest") {
+                kind = ColumnKind::Rest;
+            } else if meta.path.is_ident("skip") {
+                kind = ColumnKind::Skip;
+            } else {
+                return Err(meta.error("unrecognized #[proc(..)] attribute"));
+            }
+            Ok(())
+        })
+        .expect("malformed #[proc(..)] attribute");
+        return kind;
+    }
+    ColumnKind::Plain
+}
+
+fn is_string(ty: &Type) -> bool {
+    matches!(ty, Type::Path(p) if p.path.is_ident("String"))
+}
+
+/// Extracts `T` out of a `RangeInclusive<T>` field type, so `#[proc(range)]` can parse the
+/// bounds as whatever type the field actually holds instead of a hardcoded `isize`.
+fn range_bound_type(ty: &Type) -> &Type {
+    if let Type::Path(p) = ty {
+        if let Some(segment) = p.path.segments.last() {
+            if segment.ident == "RangeInclusive" {
+                if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
+                    if let Some(syn::GenericArgument::Type(inner)) = args.args.first() {
+                        return inner;
+                    }
+                }
+            }
+        }
+    }
+    panic!("#[proc(range)] can only be used on a RangeInclusive<T> field")
+}
+
+/// Derives `parse_line(&str) -> procfs_core::ProcResult<Self>` from a struct's field order.
+#[proc_macro_derive(ParseColumns, attributes(proc))]
+pub fn derive_parse_columns(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => panic!("ParseColumns can only be derived for structs with named fields"),
+        },
+        _ => panic!("ParseColumns can only be derived for structs"),
+    };
+
+    let mut statements = Vec::new();
+    let mut field_names = Vec::new();
+
+    for field in fields {
+        let field_name = field.ident.as_ref().expect("named field");
+        let ty = &field.ty;
+
+        match column_kind(&field.attrs) {
+            ColumnKind::Skip => {
+                field_names.push(field_name);
+                statements.push(quote! {
+                    let _ = crate::expect!(split.next());
+                    let #field_name: #ty = ::std::default::Default::default();
+                });
+            }
+            ColumnKind::Rest => {
+                field_names.push(field_name);
+                statements.push(quote! {
+                    let #field_name: #ty = split.collect::<Vec<_>>().join(" ");
+                });
+            }
+            ColumnKind::Range => {
+                field_names.push(field_name);
+                let bound_ty = range_bound_type(ty);
+                statements.push(quote! {
+                    let #field_name: #ty = {
+                        let bounds = crate::expect!(split.next());
+                        if let Some((lower, upper)) = bounds.split_once('-') {
+                            let lower = crate::from_str!(#bound_ty, lower);
+                            let upper = crate::from_str!(#bound_ty, upper);
+                            lower..=upper
+                        } else {
+                            let single = crate::from_str!(#bound_ty, bounds);
+                            single..=single
+                        }
+                    };
+                });
+            }
+            ColumnKind::Plain => {
+                field_names.push(field_name);
+                if is_string(ty) {
+                    statements.push(quote! {
+                        let #field_name: #ty = crate::expect!(split.next()).to_string();
+                    });
+                } else {
+                    statements.push(quote! {
+                        let #field_name: #ty = crate::from_str!(#ty, crate::expect!(split.next()));
+                    });
+                }
+            }
+        }
+    }
+
+    let expanded = quote! {
+        impl #name {
+            fn parse_line(line: &str) -> crate::ProcResult<Self> {
+                let mut split = line.split_whitespace();
+                #(#statements)*
+                Ok(#name {
+                    #(#field_names),*
+                })
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
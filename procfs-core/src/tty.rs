@@ -1,40 +1,44 @@
-use std::{collections::HashMap, ops::RangeInclusive};
+use std::{collections::HashMap, fmt, ops::RangeInclusive};
 
-use crate::{expect, from_str, FromBufRead, ProcError, ProcResult};
+use procfs_core_derive::ParseColumns;
 
+use crate::{FromBufRead, ProcError};
+
+#[derive(ParseColumns)]
 pub struct TttyDriver {
     pub name: String,
     pub node_name: String,
     pub major_number: isize,
+    #[proc(range)]
     pub minor_numbers: RangeInclusive<isize>,
     pub driver_type: String,
 }
 
 impl TttyDriver {
-    fn parse_line(line: &str) -> crate::ProcResult<Self> {
-        let mut split = line.split_whitespace();
-        let name = expect!(split.next()).to_string();
-        let node_name = expect!(split.next()).to_string();
-        let major_number = from_str!(isize, expect!(split.next()));
-        let bounds = expect!(split.next());
-        let minor_numbers = {
-            if let Some((lower, upper)) = bounds.split_once("-") {
-                let lower = from_str!(isize, lower);
-                let upper = from_str!(isize, upper);
-                lower..=upper
-            } else {
-                let single = from_str!(isize, bounds);
-                single..=single
-            }
-        };
-        let driver_type = expect!(split.next()).to_string();
-        Ok(TttyDriver {
-            name,
-            node_name,
-            major_number,
-            minor_numbers,
-            driver_type,
-        })
+    /// Returns true if this driver owns the device node identified by `(major, minor)`.
+    pub fn contains_dev(&self, major: isize, minor: isize) -> bool {
+        self.major_number == major && self.minor_numbers.contains(&minor)
+    }
+}
+
+impl fmt::Display for TttyDriver {
+    /// Reproduces the column layout of a `/proc/tty/drivers` line, emitting `lower-upper` when
+    /// `minor_numbers` spans more than one value and a single number otherwise.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (lower, upper) = (*self.minor_numbers.start(), *self.minor_numbers.end());
+        if lower == upper {
+            write!(
+                f,
+                "{} {} {} {} {}",
+                self.name, self.node_name, self.major_number, lower, self.driver_type
+            )
+        } else {
+            write!(
+                f,
+                "{} {} {} {}-{} {}",
+                self.name, self.node_name, self.major_number, lower, upper, self.driver_type
+            )
+        }
     }
 }
 
@@ -42,6 +46,35 @@ pub struct TtyDrivers {
     pub drivers: HashMap<String, TttyDriver>,
 }
 
+impl TtyDrivers {
+    /// Finds the driver that owns the device node identified by `(major, minor)`, for example
+    /// as obtained from a `stat()` of `/dev/pts/N` or a process's controlling terminal. If more
+    /// than one driver's range claims the same device (which the kernel shouldn't produce, but
+    /// `/proc/tty/drivers` is not guaranteed to enforce), the one that sorts first by name wins,
+    /// matching the deterministic ordering `Display` already uses.
+    pub fn driver_for(&self, major: isize, minor: isize) -> Option<&TttyDriver> {
+        let mut names: Vec<&String> = self.drivers.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| &self.drivers[name])
+            .find(|driver| driver.contains_dev(major, minor))
+    }
+}
+
+impl fmt::Display for TtyDrivers {
+    /// Writes every driver back out in `/proc/tty/drivers` column format, one per line, sorted
+    /// by name for a deterministic round trip.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&String> = self.drivers.keys().collect();
+        names.sort();
+        for name in names {
+            writeln!(f, "{}", self.drivers[name])?;
+        }
+        Ok(())
+    }
+}
+
 impl FromBufRead for TtyDrivers {
     fn from_buf_read<R: std::io::BufRead>(r: R) -> crate::ProcResult<Self> {
         let mut drivers = HashMap::new();
@@ -55,18 +88,52 @@ impl FromBufRead for TtyDrivers {
     }
 }
 
+impl TtyDrivers {
+    /// Parses each line independently, yielding a `ProcResult` per line instead of aborting on
+    /// the first malformed one.
+    pub fn iter_buf_read<R: std::io::BufRead>(r: R) -> impl Iterator<Item = crate::ProcResult<TttyDriver>> {
+        r.lines().map(|line| {
+            let line = line.map_err(|e| crate::ProcError::Other(e.to_string()))?;
+            TttyDriver::parse_line(&line)
+        })
+    }
+
+    /// Like [`iter_buf_read`](Self::iter_buf_read), but collects the successfully parsed drivers
+    /// into a `TtyDrivers` and returns the `(line index, raw line)` of every line that failed to
+    /// parse, so callers can keep going on kernels with evolving `/proc/tty/drivers` formats.
+    /// Line indices are zero-based: the first line of the input is index 0.
+    pub fn from_buf_read_lossy<R: std::io::BufRead>(r: R) -> (Self, Vec<(usize, String)>) {
+        let mut drivers = HashMap::new();
+        let mut errors = Vec::new();
+        for (line_no, line) in r.lines().enumerate() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    errors.push((line_no, e.to_string()));
+                    continue;
+                }
+            };
+            match TttyDriver::parse_line(&line) {
+                Ok(driver) => {
+                    drivers.insert(driver.name.clone(), driver);
+                }
+                Err(_) => errors.push((line_no, line)),
+            }
+        }
+        (TtyDrivers { drivers }, errors)
+    }
+}
+
+#[derive(ParseColumns)]
 pub struct LineDiscipline {
     pub name: String,
     pub no: usize,
 }
 
-impl LineDiscipline {
-    fn parse_line(line: &str) -> ProcResult<Self> {
-        let mut line = line.split_whitespace();
-        let name = expect!(line.next()).to_string();
-        let no_string = expect!(line.next());
-        let no = from_str!(usize, no_string);
-        Ok(LineDiscipline { name, no })
+impl fmt::Display for LineDiscipline {
+    /// Reproduces the column layout of a `/proc/tty/ldiscs` line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.no)
     }
 }
 
@@ -74,6 +141,17 @@ pub struct LineDisciplines {
     pub disciplines: Vec<LineDiscipline>,
 }
 
+impl fmt::Display for LineDisciplines {
+    /// Writes every line discipline back out in `/proc/tty/ldiscs` column format, one per line,
+    /// in their original order.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for discipline in &self.disciplines {
+            writeln!(f, "{}", discipline)?;
+        }
+        Ok(())
+    }
+}
+
 impl FromBufRead for LineDisciplines {
     fn from_buf_read<R: std::io::BufRead>(r: R) -> crate::ProcResult<Self> {
         let mut disciplines = Vec::new();
@@ -86,12 +164,71 @@ impl FromBufRead for LineDisciplines {
     }
 }
 
+impl LineDisciplines {
+    /// Parses each line independently, yielding a `ProcResult` per line instead of aborting on
+    /// the first malformed one.
+    pub fn iter_buf_read<R: std::io::BufRead>(r: R) -> impl Iterator<Item = crate::ProcResult<LineDiscipline>> {
+        r.lines().map(|line| {
+            let line = line.map_err(|e| ProcError::Other(e.to_string()))?;
+            LineDiscipline::parse_line(&line)
+        })
+    }
+
+    /// Like [`iter_buf_read`](Self::iter_buf_read), but collects the successfully parsed line
+    /// disciplines into a `LineDisciplines` and returns the `(line index, raw line)` of every
+    /// line that failed to parse. Line indices are zero-based: the first line of the input is
+    /// index 0.
+    pub fn from_buf_read_lossy<R: std::io::BufRead>(r: R) -> (Self, Vec<(usize, String)>) {
+        let mut disciplines = Vec::new();
+        let mut errors = Vec::new();
+        for (line_no, line) in r.lines().enumerate() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    errors.push((line_no, e.to_string()));
+                    continue;
+                }
+            };
+            match LineDiscipline::parse_line(&line) {
+                Ok(discipline) => disciplines.push(discipline),
+                Err(_) => errors.push((line_no, line)),
+            }
+        }
+        (LineDisciplines { disciplines }, errors)
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use std::ops::RangeInclusive;
+
+    use procfs_core_derive::ParseColumns;
+
     use crate::FromBufRead;
 
     use super::{LineDiscipline, LineDisciplines, TttyDriver, TtyDrivers};
 
+    #[derive(ParseColumns)]
+    struct TestRow {
+        name: String,
+        #[proc(skip)]
+        ignored: u32,
+        #[proc(range)]
+        ports: RangeInclusive<u32>,
+        #[proc(rest)]
+        comment: String,
+    }
+
+    #[test]
+    fn skip_and_rest_columns() {
+        let row = TestRow::parse_line("eth0 whatever 1024-2048 some trailing comment")
+            .expect("Did not parse test row correctly");
+        assert_eq!(&row.name, "eth0");
+        assert_eq!(row.ignored, 0);
+        assert_eq!(row.ports, 1024..=2048);
+        assert_eq!(&row.comment, "some trailing comment");
+    }
+
     #[test]
     fn correct_line_tty() {
         let line = "/dev/tty             /dev/tty        5       0 system:/dev/tty";
@@ -128,6 +265,113 @@ unknown              /dev/tty        4 1-63 console
         assert_eq!(&pty_slave_driver.driver_type, "pty:slave");
     }
 
+    #[test]
+    fn driver_for_finds_owning_driver() {
+        let file = "/dev/tty             /dev/tty        5       0 system:/dev/tty
+/dev/console         /dev/console    5       1 system:console
+/dev/ptmx            /dev/ptmx       5       2 system
+/dev/vc/0            /dev/vc/0       4       0 system:vtmaster
+ttyAMA               /dev/ttyAMA   204 64-77 serial
+ttyprintk            /dev/ttyprintk   5       3 console
+pty_slave            /dev/pts      136 0-1048575 pty:slave
+pty_master           /dev/ptm      128 0-1048575 pty:master
+unknown              /dev/tty        4 1-63 console
+";
+        let drivers = TtyDrivers::from_buf_read(file.as_bytes()).expect("Unable to parse driver file string");
+
+        let driver = drivers.driver_for(136, 42).expect("Should find the pty_slave driver");
+        assert_eq!(&driver.name, "pty_slave");
+        assert!(driver.contains_dev(136, 42));
+
+        let driver = drivers.driver_for(204, 70).expect("Should find the ttyAMA driver");
+        assert_eq!(&driver.name, "ttyAMA");
+
+        assert!(drivers.driver_for(136, 1048576).is_none());
+        assert!(drivers.driver_for(999, 0).is_none());
+    }
+
+    #[test]
+    fn lossy_parse_skips_bad_lines_and_records_them() {
+        let file = "/dev/tty             /dev/tty        5       0 system:/dev/tty
+garbage line with too few columns
+/dev/ptmx            /dev/ptmx       5       2 system
+";
+        let (drivers, errors) = TtyDrivers::from_buf_read_lossy(file.as_bytes());
+        assert_eq!(drivers.drivers.len(), 2);
+        assert!(drivers.drivers.contains_key("/dev/tty"));
+        assert!(drivers.drivers.contains_key("/dev/ptmx"));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0], (1, "garbage line with too few columns".to_string()));
+    }
+
+    #[test]
+    fn iter_buf_read_yields_a_result_per_line() {
+        let file = "/dev/tty             /dev/tty        5       0 system:/dev/tty
+garbage
+";
+        let results: Vec<_> = TtyDrivers::iter_buf_read(file.as_bytes()).collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+    }
+
+    #[test]
+    fn ldiscs_lossy_parse_skips_bad_lines_and_records_them() {
+        let file = "n_tty       0
+garbage
+n_null     27
+";
+        let (ldiscs, errors) = LineDisciplines::from_buf_read_lossy(file.as_bytes());
+        assert_eq!(ldiscs.disciplines.len(), 2);
+        assert_eq!(&ldiscs.disciplines[0].name, "n_tty");
+        assert_eq!(&ldiscs.disciplines[1].name, "n_null");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0], (1, "garbage".to_string()));
+    }
+
+    #[test]
+    fn ldiscs_iter_buf_read_yields_a_result_per_line() {
+        let file = "n_tty       0
+garbage
+";
+        let results: Vec<_> = LineDisciplines::iter_buf_read(file.as_bytes()).collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+    }
+
+    #[test]
+    fn driver_round_trips_through_display() {
+        let single = "/dev/tty             /dev/tty        5       0 system:/dev/tty";
+        let driver = TttyDriver::parse_line(single).expect("Did not parse line correctly");
+        let reparsed = TttyDriver::parse_line(&driver.to_string()).expect("Did not re-parse emitted line");
+        assert_eq!(driver.name, reparsed.name);
+        assert_eq!(driver.node_name, reparsed.node_name);
+        assert_eq!(driver.major_number, reparsed.major_number);
+        assert_eq!(driver.minor_numbers, reparsed.minor_numbers);
+        assert_eq!(driver.driver_type, reparsed.driver_type);
+
+        let ranged = "pty_slave            /dev/pts      136 0-1048575 pty:slave";
+        let driver = TttyDriver::parse_line(ranged).expect("Did not parse line correctly");
+        assert_eq!(driver.to_string(), "pty_slave /dev/pts 136 0-1048575 pty:slave");
+    }
+
+    #[test]
+    fn drivers_round_trip_through_display() {
+        let file = "/dev/tty             /dev/tty        5       0 system:/dev/tty
+/dev/ptmx            /dev/ptmx       5       2 system
+";
+        let drivers = TtyDrivers::from_buf_read(file.as_bytes()).expect("Unable to parse driver file string");
+        let emitted = drivers.to_string();
+        let reparsed = TtyDrivers::from_buf_read(emitted.as_bytes()).expect("Unable to re-parse emitted drivers");
+        assert_eq!(drivers.drivers.len(), reparsed.drivers.len());
+        for (name, driver) in &drivers.drivers {
+            let reparsed_driver = &reparsed.drivers[name];
+            assert_eq!(driver.major_number, reparsed_driver.major_number);
+            assert_eq!(driver.minor_numbers, reparsed_driver.minor_numbers);
+        }
+    }
+
     #[test]
     fn correct_ldisc_line() {
         let line = "n_tty       0";
@@ -156,4 +400,20 @@ n_null     27";
         assert_eq!(&n_null.name, "n_null");
         assert_eq!(n_null.no, 27);
     }
+
+    #[test]
+    fn ldiscs_round_trip_through_display() {
+        let file_string = "n_tty       0
+n_null     27";
+        let ldiscs =
+            LineDisciplines::from_buf_read(file_string.as_bytes()).expect("Unable to parse line discipline file string");
+        let emitted = ldiscs.to_string();
+        let reparsed =
+            LineDisciplines::from_buf_read(emitted.as_bytes()).expect("Unable to re-parse emitted line disciplines");
+        assert_eq!(reparsed.disciplines.len(), 2);
+        assert_eq!(reparsed.disciplines[0].name, "n_tty");
+        assert_eq!(reparsed.disciplines[0].no, 0);
+        assert_eq!(reparsed.disciplines[1].name, "n_null");
+        assert_eq!(reparsed.disciplines[1].no, 27);
+    }
 }